@@ -0,0 +1,340 @@
+//! A multi-peer registry that dispatches inbound messages to the right peer
+//! by static key (for handshake-initiating messages) or by receiver session
+//! index (for everything after), mirroring the dispatch design used by
+//! UDP-based Noise deployments such as WireGuard.
+//!
+//! Known scope gap: this crate has no `SymmetricState`/`HandshakeState`
+//! pipeline, so `Device::process` stops at identifying and returning the
+//! matching `Peer` rather than driving the handshake the rest of the way to
+//! a transport (`CipherState`) pair. Multiplexing by peer is the part this
+//! module actually provides; finishing a handshake into transport keys is
+//! left to the caller until a `HandshakeState` exists to wire in here.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tai64n::Tai64N;
+use traits::{Cipher, Hash, DH, U8Array};
+
+/// Message type byte identifying the first message of an `IK`-style
+/// initiation, whose payload carries an AEAD-encrypted initiator static key.
+pub const MSG_TYPE_INITIATION: u8 = 1;
+
+/// A peer known to a `Device`, identified by its static public key.
+pub struct Peer<D: DH> {
+    /// The peer's static public key.
+    pub static_pubkey: D::Pubkey,
+    /// The greatest initiation timestamp accepted from this peer so far, if any.
+    last_timestamp: Mutex<Option<Tai64N>>,
+}
+
+impl<D: DH> Peer<D> {
+    /// Construct a new peer with the given static public key.
+    pub fn new(static_pubkey: D::Pubkey) -> Self {
+        Peer {
+            static_pubkey,
+            last_timestamp: Mutex::new(None),
+        }
+    }
+
+    /// Check an initiation's embedded `Tai64N` timestamp against the
+    /// greatest one previously accepted from this peer, atomically updating
+    /// it on acceptance. Returns `false` (without updating) if `timestamp`
+    /// is not strictly greater, which means the initiation is a replay or
+    /// reordered duplicate and must be rejected before any session is created.
+    pub fn check_and_update_timestamp(&self, timestamp: Tai64N) -> bool {
+        let mut last = self.last_timestamp.lock().unwrap();
+        if last.is_none_or(|prev| timestamp > prev) {
+            *last = Some(timestamp);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Errors returned while dispatching an inbound message through a `Device`.
+#[derive(Debug)]
+pub enum DeviceError {
+    /// The message is empty, or it is an initiation that is too short, fails
+    /// to decrypt, or carries a static key that doesn't match any known peer.
+    UnknownPeer,
+    /// The message's receiver session index isn't bound to any peer.
+    UnknownSession,
+    /// The initiation's timestamp was not strictly greater than the last one
+    /// accepted from this peer.
+    Replayed,
+}
+
+/// Owns a local static keypair and a set of peers keyed by their static
+/// public key, plus a map from receiver session index to peer, so a
+/// many-peers-one-socket server can dispatch inbound messages without
+/// hand-rolling peer lookup around a single handshake state.
+pub struct Device<D: DH> {
+    local_static: D::Key,
+    local_pubkey: D::Pubkey,
+    peers: HashMap<Vec<u8>, Peer<D>>,
+    sessions: HashMap<u32, Vec<u8>>,
+}
+
+impl<D: DH> Device<D> {
+    /// Construct a `Device` for the given local static keypair.
+    pub fn new(local_static: D::Key) -> Self {
+        let local_pubkey = D::pubkey(&local_static);
+        Device {
+            local_static,
+            local_pubkey,
+            peers: HashMap::new(),
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// The local static private key.
+    pub fn local_static(&self) -> &D::Key {
+        &self.local_static
+    }
+
+    /// The local static public key.
+    pub fn local_pubkey(&self) -> &D::Pubkey {
+        &self.local_pubkey
+    }
+
+    /// Register a peer, keyed by its static public key.
+    pub fn add_peer(&mut self, peer: Peer<D>) {
+        self.peers.insert(peer.static_pubkey.as_slice().to_vec(), peer);
+    }
+
+    /// Remove a peer by static public key.
+    pub fn remove_peer(&mut self, static_pubkey: &D::Pubkey) -> Option<Peer<D>> {
+        self.peers.remove(static_pubkey.as_slice())
+    }
+
+    /// Look up a peer by static public key.
+    pub fn get_peer(&self, static_pubkey: &D::Pubkey) -> Option<&Peer<D>> {
+        self.peers.get(static_pubkey.as_slice())
+    }
+
+    /// Record that `index` is the local receiver index for the session with
+    /// the peer identified by `static_pubkey`.
+    pub fn bind_session(&mut self, index: u32, static_pubkey: &D::Pubkey) {
+        self.sessions.insert(index, static_pubkey.as_slice().to_vec());
+    }
+
+    /// Drop a session index binding, e.g. once the session expires.
+    pub fn unbind_session(&mut self, index: u32) {
+        self.sessions.remove(&index);
+    }
+
+    /// Look up the peer a receiver session index is bound to.
+    pub fn peer_for_session(&self, index: u32) -> Option<&Peer<D>> {
+        self.sessions.get(&index).and_then(|k| self.peers.get(k))
+    }
+
+    /// Dispatch an inbound message to the peer it belongs to.
+    ///
+    /// `msg[0]` is a message type byte. Anything but `MSG_TYPE_INITIATION`
+    /// carries a big-endian `u32` receiver session index in `msg[1..5]`,
+    /// matched against the sessions bound with `bind_session`.
+    ///
+    /// `MSG_TYPE_INITIATION` messages (the first message of an `IK`-style
+    /// pattern) carry no session index yet, since the initiator's identity
+    /// is hidden: `msg[1..]` is the initiator's ephemeral public key in the
+    /// clear, followed by its static public key AEAD-encrypted under
+    /// `HASH(DH(local_static, e))` with nonce `0` and no associated data --
+    /// the key a responder can derive from its own static key and the
+    /// message alone, before it knows who the initiator is. `process`
+    /// performs that decryption and looks the result up in `peers`.
+    ///
+    /// Continuing the handshake past dispatch (mixing the result into a
+    /// running `SymmetricState` and eventually producing a transport state)
+    /// requires a `HandshakeState`, which this crate doesn't expose yet.
+    pub fn process<C: Cipher, H: Hash>(&self, msg: &[u8]) -> Result<&Peer<D>, DeviceError> {
+        let msg_type = *msg.first().ok_or(DeviceError::UnknownPeer)?;
+        let body = &msg[1..];
+
+        if msg_type != MSG_TYPE_INITIATION {
+            if body.len() < 4 {
+                return Err(DeviceError::UnknownSession);
+            }
+            let index = u32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+            return self.peer_for_session(index).ok_or(DeviceError::UnknownSession);
+        }
+
+        let e_len = D::Pubkey::len();
+        let s_ct_len = D::Pubkey::len() + C::tag_len();
+        if body.len() < e_len + s_ct_len {
+            return Err(DeviceError::UnknownPeer);
+        }
+        let e = D::Pubkey::from_slice(&body[..e_len]);
+        let es = D::dh(&self.local_static, &e);
+        let key_hash = H::hash(es.as_slice());
+        let key = C::Key::from_slice(&key_hash.as_slice()[..C::key_len()]);
+
+        let mut s_plain = vec![0u8; D::Pubkey::len()];
+        C::decrypt(&key, 0, &[], &body[e_len..e_len + s_ct_len], &mut s_plain)
+            .map_err(|_| DeviceError::UnknownPeer)?;
+        let initiator_pubkey = D::Pubkey::from_slice(&s_plain);
+
+        self.get_peer(&initiator_pubkey).ok_or(DeviceError::UnknownPeer)
+    }
+
+    /// Handle the first message of an identity-hiding (`IK`/`K`-style)
+    /// initiation, once the caller has decrypted the initiator's static key
+    /// and the `Tai64N` timestamp carried in its payload.
+    ///
+    /// Looks up the peer by `initiator_pubkey` and rejects the initiation
+    /// before any session is created if `timestamp` isn't strictly greater
+    /// than the last one accepted from that peer, so a replayed or
+    /// reordered initiation can't force a redundant DH computation.
+    pub fn process_initiation(
+        &self,
+        initiator_pubkey: &D::Pubkey,
+        timestamp: Tai64N,
+    ) -> Result<&Peer<D>, DeviceError> {
+        let peer = self.get_peer(initiator_pubkey).ok_or(DeviceError::UnknownPeer)?;
+        if peer.check_and_update_timestamp(timestamp) {
+            Ok(peer)
+        } else {
+            Err(DeviceError::Replayed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone)]
+    struct MockDH;
+
+    impl DH for MockDH {
+        type Key = [u8; 32];
+        type Pubkey = [u8; 32];
+        type Output = [u8; 32];
+
+        fn name() -> &'static str {
+            "mock"
+        }
+        fn genkey() -> Self::Key {
+            [0u8; 32]
+        }
+        fn pubkey(k: &Self::Key) -> Self::Pubkey {
+            *k
+        }
+        fn dh(k: &Self::Key, p: &Self::Pubkey) -> Self::Output {
+            let mut out = [0u8; 32];
+            for i in 0..32 {
+                out[i] = k[i] ^ p[i];
+            }
+            out
+        }
+    }
+
+    struct MockCipher;
+
+    impl Cipher for MockCipher {
+        fn name() -> &'static str {
+            "mock"
+        }
+        type Key = [u8; 32];
+
+        fn encrypt(k: &Self::Key, _nonce: u64, _ad: &[u8], plaintext: &[u8], out: &mut [u8]) {
+            for i in 0..plaintext.len() {
+                out[i] = plaintext[i] ^ k.as_slice()[i % 32];
+            }
+            for b in &mut out[plaintext.len()..] {
+                *b = 0;
+            }
+        }
+
+        fn decrypt(
+            k: &Self::Key,
+            _nonce: u64,
+            _ad: &[u8],
+            ciphertext: &[u8],
+            out: &mut [u8],
+        ) -> Result<(), ()> {
+            let tag_start = ciphertext.len() - Self::tag_len();
+            if ciphertext[tag_start..].iter().any(|&b| b != 0) {
+                return Err(());
+            }
+            for i in 0..tag_start {
+                out[i] = ciphertext[i] ^ k.as_slice()[i % 32];
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct MockHash {
+        state: [u8; 32],
+    }
+
+    impl Hash for MockHash {
+        fn name() -> &'static str {
+            "mock"
+        }
+        type Block = [u8; 64];
+        type Output = [u8; 32];
+
+        fn input(&mut self, data: &[u8]) {
+            for (i, b) in data.iter().enumerate() {
+                self.state[i % 32] ^= b.wrapping_add(i as u8);
+            }
+        }
+
+        fn result(&mut self) -> Self::Output {
+            self.state
+        }
+    }
+
+    #[test]
+    fn process_dispatches_initiation_to_the_matching_peer() {
+        let local_static = [1u8; 32];
+        let mut device = Device::<MockDH>::new(local_static);
+
+        let peer_static = [2u8; 32];
+        device.add_peer(Peer::new(peer_static));
+
+        let e = [3u8; 32];
+        let es = MockDH::dh(&local_static, &e);
+        let key_hash = MockHash::hash(es.as_slice());
+        let key = <MockCipher as Cipher>::Key::from_slice(&key_hash.as_slice()[..32]);
+
+        let mut s_ct = vec![0u8; 32 + MockCipher::tag_len()];
+        MockCipher::encrypt(&key, 0, &[], &peer_static, &mut s_ct);
+
+        let mut msg = vec![MSG_TYPE_INITIATION];
+        msg.extend_from_slice(&e);
+        msg.extend_from_slice(&s_ct);
+
+        let found = device.process::<MockCipher, MockHash>(&msg).unwrap();
+        assert_eq!(found.static_pubkey, peer_static);
+    }
+
+    #[test]
+    fn process_dispatches_transport_messages_by_session_index() {
+        let mut device = Device::<MockDH>::new([1u8; 32]);
+        let peer_static = [2u8; 32];
+        device.add_peer(Peer::new(peer_static));
+        device.bind_session(42, &peer_static);
+
+        let mut msg = vec![2u8];
+        msg.extend_from_slice(&42u32.to_be_bytes());
+
+        let found = device.process::<MockCipher, MockHash>(&msg).unwrap();
+        assert_eq!(found.static_pubkey, peer_static);
+    }
+
+    #[test]
+    fn process_rejects_unknown_session() {
+        let device = Device::<MockDH>::new([1u8; 32]);
+        let mut msg = vec![2u8];
+        msg.extend_from_slice(&42u32.to_be_bytes());
+
+        assert!(matches!(
+            device.process::<MockCipher, MockHash>(&msg),
+            Err(DeviceError::UnknownSession)
+        ));
+    }
+}
@@ -4,7 +4,7 @@ use self::arrayvec::ArrayVec;
 
 /// A token in noise message patterns.
 #[allow(missing_docs)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Token {
     E,
     S,
@@ -18,7 +18,7 @@ pub enum Token {
 use self::Token::*;
 
 /// Noise handshake pattern.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct HandshakePattern {
     pre_i: ArrayVec<[Token; 4]>,
     pre_r: ArrayVec<[Token; 4]>,
@@ -38,18 +38,18 @@ impl HandshakePattern {
     /// If any of the patterns are too long (longer than 8 tokens).
     ///
     /// Or if the number of patterns are too large (larger than 8).
-    pub fn new<'a>(
+    pub fn new(
         pre_i: &[Token],
         pre_r: &[Token],
         msg_patterns: &[&[Token]],
         name: &'static str,
     ) -> Self {
         HandshakePattern {
-            pre_i: pre_i.into_iter().cloned().collect(),
-            pre_r: pre_r.into_iter().cloned().collect(),
+            pre_i: pre_i.iter().cloned().collect(),
+            pre_r: pre_r.iter().cloned().collect(),
             msg_patterns: msg_patterns
-                .into_iter()
-                .map(|p| p.into_iter().cloned().collect())
+                .iter()
+                .map(|p| p.iter().cloned().collect())
                 .collect(),
             name,
         }
@@ -82,15 +82,236 @@ impl HandshakePattern {
 
     /// Whether there are any psk tokens in this pattern.
     pub fn has_psk(&self) -> bool {
-        self.msg_patterns.iter().any(|m| {
-            m.iter().any(|m| match m {
-                Token::PSK => true,
-                _ => false,
-            })
-        })
+        self.msg_patterns
+            .iter()
+            .any(|m| m.iter().any(|m| matches!(m, Token::PSK)))
+    }
+
+    /// Parse a full Noise protocol pattern name, e.g. `XXpsk2`, `IKpsk1` or
+    /// `NNpsk0+psk2`, into the corresponding `HandshakePattern`.
+    ///
+    /// The `pskN` modifiers insert a `PSK` token into the pattern: `psk0`
+    /// prepends a `PSK` to the first message pattern, and `pskN` for `N >= 1`
+    /// appends a `PSK` to the `N`-th (1-indexed) message pattern. Modifiers
+    /// are applied in the order they appear in `name`.
+    pub fn from_name(name: &str) -> Result<Self, NameError> {
+        let split = name.find("psk").unwrap_or(name.len());
+        let (base, mods) = name.split_at(split);
+
+        let mut pattern = base_pattern_from_name(base).ok_or(NameError::UnknownPattern)?;
+
+        if !mods.is_empty() {
+            for modifier in mods.split('+') {
+                let n = modifier
+                    .strip_prefix("psk")
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .ok_or(NameError::UnknownModifier)?;
+                pattern.insert_psk(n)?;
+            }
+        }
+
+        pattern.validate()?;
+
+        Ok(pattern)
+    }
+
+    /// Insert a `PSK` token as described by `from_name`'s `pskN` modifier.
+    fn insert_psk(&mut self, n: usize) -> Result<(), NameError> {
+        if n == 0 {
+            let first = self.msg_patterns.get_mut(0).ok_or(NameError::InvalidModifier)?;
+            if first.is_full() {
+                return Err(NameError::PatternFull);
+            }
+            first.insert(0, PSK);
+        } else {
+            let msg = self.msg_patterns
+                .get_mut(n - 1)
+                .ok_or(NameError::InvalidModifier)?;
+            msg.try_push(PSK).map_err(|_| NameError::PatternFull)?;
+        }
+        Ok(())
+    }
+
+    /// Check that this pattern follows the Noise spec's rules for
+    /// well-formed handshake patterns:
+    ///
+    /// - A party sends its `e` or `s` at most once.
+    /// - `ee`, `es`, `se` and `ss` each appear at most once across the whole
+    ///   pattern.
+    /// - A DH token is only used once both of the keys it requires have been
+    ///   established, either via a pre-message or an earlier token.
+    ///
+    /// This is the check that `HandshakePattern::new` leaves up to the
+    /// caller; `from_name` and custom-built patterns can run it to catch
+    /// mistakes early.
+    pub fn validate(&self) -> Result<(), PatternError> {
+        let mut i_e = self.pre_i.contains(&E);
+        let mut i_s = self.pre_i.contains(&S);
+        let mut r_e = self.pre_r.contains(&E);
+        let mut r_s = self.pre_r.contains(&S);
+        let (mut ee, mut es, mut se, mut ss) = (false, false, false, false);
+
+        for (message, tokens) in self.msg_patterns.iter().enumerate() {
+            // Messages strictly alternate, starting with the initiator.
+            let initiator_sends = message % 2 == 0;
+
+            if tokens.is_empty() {
+                return Err(PatternError::EmptyMessage { message });
+            }
+            let last_index = tokens.len() - 1;
+
+            for (token_index, &token) in tokens.iter().enumerate() {
+                match token {
+                    E => {
+                        let has = if initiator_sends { &mut i_e } else { &mut r_e };
+                        if *has {
+                            return Err(PatternError::DuplicateKey { message, token });
+                        }
+                        *has = true;
+                    }
+                    S => {
+                        let has = if initiator_sends { &mut i_s } else { &mut r_s };
+                        if *has {
+                            return Err(PatternError::DuplicateKey { message, token });
+                        }
+                        *has = true;
+                    }
+                    EE => {
+                        if ee {
+                            return Err(PatternError::DuplicateDh { message, token });
+                        }
+                        if !(i_e && r_e) {
+                            return Err(PatternError::PrematureDh { message, token });
+                        }
+                        ee = true;
+                    }
+                    ES => {
+                        if es {
+                            return Err(PatternError::DuplicateDh { message, token });
+                        }
+                        if !(i_e && r_s) {
+                            return Err(PatternError::PrematureDh { message, token });
+                        }
+                        es = true;
+                    }
+                    SE => {
+                        if se {
+                            return Err(PatternError::DuplicateDh { message, token });
+                        }
+                        if !(i_s && r_e) {
+                            return Err(PatternError::PrematureDh { message, token });
+                        }
+                        se = true;
+                    }
+                    SS => {
+                        if ss {
+                            return Err(PatternError::DuplicateDh { message, token });
+                        }
+                        if !(i_s && r_s) {
+                            return Err(PatternError::PrematureDh { message, token });
+                        }
+                        ss = true;
+                    }
+                    PSK => {
+                        // `psk0` (prepended to message 0) and `pskN` for
+                        // `N >= 1` (appended to message `N - 1`) are the
+                        // only placements `insert_psk` produces; anywhere
+                        // else means the pattern can't have come from
+                        // `from_name` and isn't a well-formed PSK pattern.
+                        let is_psk0 = message == 0 && token_index == 0;
+                        let is_pskn = token_index == last_index;
+                        if !is_psk0 && !is_pskn {
+                            return Err(PatternError::InvalidPsk { message, token });
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Error returned by `HandshakePattern::validate` identifying the offending
+/// token and the message it appears in.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternError {
+    /// A party sent the same key (`e` or `s`) more than once.
+    DuplicateKey { message: usize, token: Token },
+    /// A DH token (`ee`/`es`/`se`/`ss`) appeared more than once in the pattern.
+    DuplicateDh { message: usize, token: Token },
+    /// A DH token was used before both of the keys it requires were established.
+    PrematureDh { message: usize, token: Token },
+    /// A `PSK` token appeared somewhere other than the start of the first
+    /// message or the end of a message.
+    InvalidPsk { message: usize, token: Token },
+    /// A message pattern carries no tokens at all.
+    EmptyMessage { message: usize },
+}
+
+/// Error returned when parsing a Noise protocol pattern name fails.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameError {
+    /// The base pattern portion of the name is not a known pattern.
+    UnknownPattern,
+    /// A `pskN` modifier was malformed.
+    UnknownModifier,
+    /// A `pskN` modifier refers to a message pattern that doesn't exist.
+    InvalidModifier,
+    /// A `pskN` modifier's message pattern is already full (8 tokens).
+    PatternFull,
+    /// The pattern, after its `pskN` modifiers are applied, is not well-formed.
+    Invalid(PatternError),
+}
+
+impl From<PatternError> for NameError {
+    fn from(e: PatternError) -> Self {
+        NameError::Invalid(e)
     }
 }
 
+/// Look up one of the built-in base patterns (without any `psk` modifiers) by name.
+fn base_pattern_from_name(name: &str) -> Option<HandshakePattern> {
+    Some(match name {
+        "N" => noise_n(),
+        "K" => noise_k(),
+        "X" => noise_x(),
+        "NN" => noise_nn(),
+        "NK" => noise_nk(),
+        "NX" => noise_nx(),
+        "XN" => noise_xn(),
+        "XK" => noise_xk(),
+        "XX" => noise_xx(),
+        "KN" => noise_kn(),
+        "KK" => noise_kk(),
+        "KX" => noise_kx(),
+        "IN" => noise_in(),
+        "IK" => noise_ik(),
+        "IX" => noise_ix(),
+        "XXfallback" => noise_xx_fallback(),
+        "NK1" => noise_nk1(),
+        "NX1" => noise_nx1(),
+        "X1N" => noise_x1n(),
+        "X1K" => noise_x1k(),
+        "XK1" => noise_xk1(),
+        "X1X" => noise_x1x(),
+        "XX1" => noise_xx1(),
+        "K1N" => noise_k1n(),
+        "K1K" => noise_k1k(),
+        "KK1" => noise_kk1(),
+        "K1X" => noise_k1x(),
+        "KX1" => noise_kx1(),
+        "I1N" => noise_i1n(),
+        "I1K" => noise_i1k(),
+        "IK1" => noise_ik1(),
+        "I1X" => noise_i1x(),
+        "IX1" => noise_ix1(),
+        _ => return None,
+    })
+}
+
 macro_rules! vec {
     () => {
         ArrayVec::new()
@@ -267,3 +488,316 @@ pub fn noise_xx_fallback() -> HandshakePattern {
         name: "XXfallback",
     }
 }
+
+// The deferred patterns below postpone a static-key DH token to the message
+// immediately following the one where the base pattern would send it. A "1"
+// after a party's letter marks that party's authentication as deferred, e.g.
+// `NK1` defers the `es` of `NK`, and `XK1`/`X1K` defer the `es`/`se` of `XK`
+// independently. `K1K`, `KK1`, `I1K` and `IK1` also drop the `ss` token that
+// their base patterns `KK` and `IK` carry.
+
+/// The `Noise_NK1` pattern.
+pub fn noise_nk1() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![S],
+        msg_patterns: vec![vec![E], vec![E, EE, ES]],
+        name: "NK1",
+    }
+}
+
+/// The `Noise_NX1` pattern.
+pub fn noise_nx1() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E], vec![E, EE, S], vec![ES]],
+        name: "NX1",
+    }
+}
+
+/// The `Noise_X1N` pattern.
+pub fn noise_x1n() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E], vec![E, EE], vec![S], vec![SE]],
+        name: "X1N",
+    }
+}
+
+/// The `Noise_X1K` pattern.
+pub fn noise_x1k() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![S],
+        msg_patterns: vec![vec![E, ES], vec![E, EE], vec![S], vec![SE]],
+        name: "X1K",
+    }
+}
+
+/// The `Noise_XK1` pattern.
+pub fn noise_xk1() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![S],
+        msg_patterns: vec![vec![E], vec![E, EE, ES], vec![S, SE]],
+        name: "XK1",
+    }
+}
+
+/// The `Noise_X1X` pattern.
+pub fn noise_x1x() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E], vec![E, EE, S, ES], vec![S], vec![SE]],
+        name: "X1X",
+    }
+}
+
+/// The `Noise_XX1` pattern.
+pub fn noise_xx1() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E], vec![E, EE, S], vec![ES, S, SE]],
+        name: "XX1",
+    }
+}
+
+/// The `Noise_K1N` pattern.
+pub fn noise_k1n() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![S],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E], vec![E, EE], vec![SE]],
+        name: "K1N",
+    }
+}
+
+/// The `Noise_K1K` pattern.
+pub fn noise_k1k() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![S],
+        pre_r: vec![S],
+        msg_patterns: vec![vec![E, ES], vec![E, EE], vec![SE]],
+        name: "K1K",
+    }
+}
+
+/// The `Noise_KK1` pattern.
+pub fn noise_kk1() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![S],
+        pre_r: vec![S],
+        msg_patterns: vec![vec![E], vec![E, EE, SE, ES]],
+        name: "KK1",
+    }
+}
+
+/// The `Noise_K1X` pattern.
+pub fn noise_k1x() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![S],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E], vec![E, EE, S, ES], vec![SE]],
+        name: "K1X",
+    }
+}
+
+/// The `Noise_KX1` pattern.
+pub fn noise_kx1() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![S],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E], vec![E, EE, SE, S], vec![ES]],
+        name: "KX1",
+    }
+}
+
+/// The `Noise_I1N` pattern.
+pub fn noise_i1n() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E, S], vec![E, EE], vec![SE]],
+        name: "I1N",
+    }
+}
+
+/// The `Noise_I1K` pattern.
+pub fn noise_i1k() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![S],
+        msg_patterns: vec![vec![E, ES, S], vec![E, EE], vec![SE]],
+        name: "I1K",
+    }
+}
+
+/// The `Noise_IK1` pattern.
+pub fn noise_ik1() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![S],
+        msg_patterns: vec![vec![E, S], vec![E, EE, SE, ES]],
+        name: "IK1",
+    }
+}
+
+/// The `Noise_I1X` pattern.
+pub fn noise_i1x() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E, S], vec![E, EE, S, ES], vec![SE]],
+        name: "I1X",
+    }
+}
+
+/// The `Noise_IX1` pattern.
+pub fn noise_ix1() -> HandshakePattern {
+    HandshakePattern {
+        pre_i: vec![],
+        pre_r: vec![],
+        msg_patterns: vec![vec![E, S], vec![E, EE, SE, S], vec![ES]],
+        name: "IX1",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(name, pre_i, pre_r, msg_patterns)` for every built-in base pattern,
+    /// transcribed from the Noise spec (§7/§9/§10) so a wrong token sequence
+    /// — not just a wrong name or a `validate()` pass — fails the test.
+    #[allow(clippy::type_complexity)]
+    fn spec_patterns() -> Vec<(&'static str, &'static [Token], &'static [Token], Vec<&'static [Token]>)> {
+        std::vec![
+            ("N", &[], &[S][..], std::vec![&[E, ES][..]]),
+            ("K", &[S], &[S][..], std::vec![&[E, ES, SS][..]]),
+            ("X", &[], &[S][..], std::vec![&[E, ES, S, SS][..]]),
+            ("NN", &[], &[], std::vec![&[E][..], &[E, EE][..]]),
+            ("NK", &[], &[S][..], std::vec![&[E, ES][..], &[E, EE][..]]),
+            ("NX", &[], &[], std::vec![&[E][..], &[E, EE, S, ES][..]]),
+            ("XN", &[], &[], std::vec![&[E][..], &[E, EE][..], &[S, SE][..]]),
+            ("XK", &[], &[S][..], std::vec![&[E, ES][..], &[E, EE][..], &[S, SE][..]]),
+            ("XX", &[], &[], std::vec![&[E][..], &[E, EE, S, ES][..], &[S, SE][..]]),
+            ("KN", &[S], &[], std::vec![&[E][..], &[E, EE, SE][..]]),
+            ("KK", &[S], &[S][..], std::vec![&[E, ES, SS][..], &[E, EE, SE][..]]),
+            ("KX", &[S], &[], std::vec![&[E][..], &[E, EE, SE, S, ES][..]]),
+            ("IN", &[], &[], std::vec![&[E, S][..], &[E, EE, SE][..]]),
+            ("IK", &[], &[S][..], std::vec![&[E, ES, S, SS][..], &[E, EE, SE][..]]),
+            ("IX", &[], &[], std::vec![&[E, S][..], &[E, EE, SE, S, ES][..]]),
+            ("XXfallback", &[], &[E][..], std::vec![&[E, EE, S, SE][..], &[S, ES][..]]),
+            ("NK1", &[], &[S][..], std::vec![&[E][..], &[E, EE, ES][..]]),
+            ("NX1", &[], &[], std::vec![&[E][..], &[E, EE, S][..], &[ES][..]]),
+            ("X1N", &[], &[], std::vec![&[E][..], &[E, EE][..], &[S][..], &[SE][..]]),
+            ("X1K", &[], &[S][..], std::vec![&[E, ES][..], &[E, EE][..], &[S][..], &[SE][..]]),
+            ("XK1", &[], &[S][..], std::vec![&[E][..], &[E, EE, ES][..], &[S, SE][..]]),
+            ("X1X", &[], &[], std::vec![&[E][..], &[E, EE, S, ES][..], &[S][..], &[SE][..]]),
+            ("XX1", &[], &[], std::vec![&[E][..], &[E, EE, S][..], &[ES, S, SE][..]]),
+            ("K1N", &[S], &[], std::vec![&[E][..], &[E, EE][..], &[SE][..]]),
+            ("K1K", &[S], &[S][..], std::vec![&[E, ES][..], &[E, EE][..], &[SE][..]]),
+            ("KK1", &[S], &[S][..], std::vec![&[E][..], &[E, EE, SE, ES][..]]),
+            ("K1X", &[S], &[], std::vec![&[E][..], &[E, EE, S, ES][..], &[SE][..]]),
+            ("KX1", &[S], &[], std::vec![&[E][..], &[E, EE, SE, S][..], &[ES][..]]),
+            ("I1N", &[], &[], std::vec![&[E, S][..], &[E, EE][..], &[SE][..]]),
+            ("I1K", &[], &[S][..], std::vec![&[E, ES, S][..], &[E, EE][..], &[SE][..]]),
+            ("IK1", &[], &[S][..], std::vec![&[E, S][..], &[E, EE, SE, ES][..]]),
+            ("I1X", &[], &[], std::vec![&[E, S][..], &[E, EE, S, ES][..], &[SE][..]]),
+            ("IX1", &[], &[], std::vec![&[E, S][..], &[E, EE, SE, S][..], &[ES][..]]),
+        ]
+    }
+
+    #[test]
+    fn from_name_round_trips_base_patterns() {
+        for (name, pre_i, pre_r, msgs) in spec_patterns() {
+            let pattern = HandshakePattern::from_name(name).unwrap();
+            assert_eq!(pattern.get_name(), name);
+            assert!(!pattern.has_psk());
+            assert_eq!(pattern.get_pre_i(), pre_i, "{}: wrong pre_i", name);
+            assert_eq!(pattern.get_pre_r(), pre_r, "{}: wrong pre_r", name);
+            assert_eq!(
+                pattern.get_message_patterns_len(),
+                msgs.len(),
+                "{}: wrong number of messages",
+                name
+            );
+            for (i, expected) in msgs.iter().enumerate() {
+                assert_eq!(
+                    pattern.get_message_pattern(i),
+                    *expected,
+                    "{}: wrong tokens in message {}",
+                    name,
+                    i
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn from_name_applies_psk_modifiers() {
+        let pattern = HandshakePattern::from_name("NNpsk0+psk2").unwrap();
+        assert!(pattern.has_psk());
+        assert_eq!(pattern.get_message_pattern(0)[0], PSK);
+        let last = pattern.get_message_pattern(1);
+        assert_eq!(last[last.len() - 1], PSK);
+    }
+
+    #[test]
+    fn from_name_rejects_unknown_pattern_and_modifier() {
+        assert_eq!(
+            HandshakePattern::from_name("ZZ").unwrap_err(),
+            NameError::UnknownPattern
+        );
+        assert_eq!(
+            HandshakePattern::from_name("NNpsk9").unwrap_err(),
+            NameError::InvalidModifier
+        );
+    }
+
+    #[test]
+    fn all_base_patterns_validate() {
+        for (name, ..) in spec_patterns() {
+            let pattern = base_pattern_from_name(name).unwrap();
+            assert_eq!(pattern.validate(), Ok(()), "{} failed to validate", name);
+        }
+    }
+
+    #[test]
+    fn validate_rejects_an_empty_message_instead_of_panicking() {
+        let broken = HandshakePattern {
+            pre_i: vec![],
+            pre_r: vec![],
+            msg_patterns: vec![vec![]],
+            name: "broken",
+        };
+        assert_eq!(
+            broken.validate(),
+            Err(PatternError::EmptyMessage { message: 0 })
+        );
+    }
+
+    #[test]
+    fn validate_rejects_a_dh_token_used_before_its_keys_are_established() {
+        // A K1K/I1K-style mistake: `se` is reachable only once the
+        // initiator's static key has been sent, but here it's never sent at
+        // all, so `se` in message 1 runs with `i_s` unestablished.
+        let broken = HandshakePattern {
+            pre_i: vec![],
+            pre_r: vec![S],
+            msg_patterns: vec![vec![E, ES], vec![E, EE, SE]],
+            name: "broken",
+        };
+        assert_eq!(
+            broken.validate(),
+            Err(PatternError::PrematureDh {
+                message: 1,
+                token: SE,
+            })
+        );
+    }
+}
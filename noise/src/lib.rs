@@ -0,0 +1,9 @@
+//! Noise protocol building blocks: handshake patterns and the crypto traits
+//! they're built from, plus a multi-peer `Device` registry, a `mac1`/cookie
+//! anti-DoS layer and TAI64N timestamps.
+
+pub mod device;
+pub mod handshakepattern;
+pub mod mac;
+pub mod tai64n;
+pub mod traits;
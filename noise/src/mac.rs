@@ -0,0 +1,209 @@
+//! WireGuard-style `mac1`/`mac2` fields, letting a responder cheaply reject
+//! handshake messages that aren't addressed to it, and a cookie mechanism
+//! for shedding load from spoofed senders without doing any DH.
+//!
+//! Both mechanisms are layered on top of a `HandshakePattern`'s wire format;
+//! they don't require any change to the patterns themselves.
+
+use traits::{Cipher, Hash, U8Array};
+
+/// Length in bytes of the `mac1` and `mac2` fields.
+pub const MAC_LEN: usize = 16;
+
+/// Labels and timing parameters for the mac1/cookie layer.
+pub struct MacConfig {
+    /// Label mixed into the `mac1` key, alongside the responder's static public key.
+    pub label_mac1: &'static [u8],
+    /// Label mixed into the cookie key, alongside the responder's static public key.
+    pub label_cookie: &'static [u8],
+    /// How often the responder's cookie secret should be rotated, in seconds.
+    pub cookie_rotation_interval: u64,
+}
+
+impl Default for MacConfig {
+    fn default() -> Self {
+        MacConfig {
+            label_mac1: b"mac1----",
+            label_cookie: b"cookie--",
+            cookie_rotation_interval: 120,
+        }
+    }
+}
+
+/// Per-peer keys derived from a responder's static public key, used to
+/// append and verify `mac1`/`mac2` on messages exchanged with that peer.
+pub struct MacKeys<H: Hash> {
+    mac1_key: H::Output,
+    cookie_key: H::Output,
+}
+
+impl<H: Hash> MacKeys<H> {
+    /// Derive the mac1/cookie keys for the peer with `responder_static_pubkey`.
+    pub fn new(responder_static_pubkey: &[u8], config: &MacConfig) -> Self {
+        MacKeys {
+            mac1_key: keyed_label::<H>(config.label_mac1, responder_static_pubkey),
+            cookie_key: keyed_label::<H>(config.label_cookie, responder_static_pubkey),
+        }
+    }
+
+    /// Append `mac1` to `msg`, writing the result into `out`.
+    ///
+    /// `out.len()` must be `msg.len() + MAC_LEN`.
+    pub fn append_mac1(&self, msg: &[u8], out: &mut [u8]) {
+        out[..msg.len()].copy_from_slice(msg);
+        let mac1 = keyed_hash::<H>(self.mac1_key.as_slice(), &out[..msg.len()]);
+        out[msg.len()..msg.len() + MAC_LEN].copy_from_slice(&mac1);
+    }
+
+    /// Append `mac1` and `mac2` to `msg`, writing the result into `out`.
+    ///
+    /// `mac2` is computed over `msg || mac1`, keyed by a `cookie` issued by
+    /// the responder (see `issue_cookie`). `out.len()` must be
+    /// `msg.len() + 2 * MAC_LEN`.
+    pub fn append_macs(&self, msg: &[u8], cookie: &[u8], out: &mut [u8]) {
+        self.append_mac1(msg, &mut out[..msg.len() + MAC_LEN]);
+        let mac2 = keyed_hash::<H>(cookie, &out[..msg.len() + MAC_LEN]);
+        out[msg.len() + MAC_LEN..msg.len() + 2 * MAC_LEN].copy_from_slice(&mac2);
+    }
+
+    /// Verify the `mac1` field of a received message.
+    ///
+    /// `mac1_offset` is where the `mac1` field starts within `msg`, i.e. the
+    /// length of the message body before any macs: `msg.len() - MAC_LEN` if
+    /// `msg` carries `mac1` only, or `msg.len() - 2 * MAC_LEN` if it also
+    /// carries a trailing `mac2` (as WireGuard's `mac1_offset`/`mac2_offset`
+    /// do), since a plain trailing-`MAC_LEN` slice would otherwise pick up
+    /// `mac2` instead.
+    pub fn verify_mac1(&self, msg: &[u8], mac1_offset: usize) -> bool {
+        if msg.len() < mac1_offset + MAC_LEN {
+            return false;
+        }
+        let mac1 = keyed_hash::<H>(self.mac1_key.as_slice(), &msg[..mac1_offset]);
+        constant_time_eq(&mac1, &msg[mac1_offset..mac1_offset + MAC_LEN])
+    }
+
+    /// Verify the trailing `mac2` field of a received message against a
+    /// `cookie` previously issued to the sender.
+    ///
+    /// `msg` must include the trailing `mac1` and `mac2`.
+    pub fn verify_mac2(&self, msg: &[u8], cookie: &[u8]) -> bool {
+        if msg.len() < 2 * MAC_LEN {
+            return false;
+        }
+        let split = msg.len() - MAC_LEN;
+        let mac2 = keyed_hash::<H>(cookie, &msg[..split]);
+        constant_time_eq(&mac2, &msg[split..])
+    }
+
+    /// Issue a cookie for a sender identified by `source_addr`, keyed by the
+    /// responder's current rotating `secret`.
+    pub fn issue_cookie(&self, secret: &H::Output, source_addr: &[u8]) -> H::Output {
+        H::hmac(secret.as_slice(), source_addr)
+    }
+
+    /// Encrypt a cookie into a cookie-reply payload, as sent back to an
+    /// initiator under load. `out.len()` must be `H::hash_len() + C::tag_len()`.
+    pub fn encrypt_cookie<C: Cipher>(&self, cookie: &H::Output, nonce: u64, ad: &[u8], out: &mut [u8]) {
+        let key = C::Key::from_slice(&self.cookie_key.as_slice()[..C::key_len()]);
+        C::encrypt(&key, nonce, ad, cookie.as_slice(), out);
+    }
+
+    /// Decrypt a cookie-reply payload produced by `encrypt_cookie`.
+    #[allow(clippy::result_unit_err)]
+    pub fn decrypt_cookie<C: Cipher>(
+        &self,
+        nonce: u64,
+        ad: &[u8],
+        ciphertext: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), ()> {
+        let key = C::Key::from_slice(&self.cookie_key.as_slice()[..C::key_len()]);
+        C::decrypt(&key, nonce, ad, ciphertext, out)
+    }
+}
+
+fn keyed_label<H: Hash>(label: &[u8], pubkey: &[u8]) -> H::Output {
+    let mut h: H = Default::default();
+    h.input(label);
+    h.input(pubkey);
+    h.result()
+}
+
+fn keyed_hash<H: Hash>(key: &[u8], data: &[u8]) -> [u8; MAC_LEN] {
+    let full = H::hmac(key, data);
+    let mut out = [0u8; MAC_LEN];
+    out.copy_from_slice(&full.as_slice()[..MAC_LEN]);
+    out
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct MockHash {
+        state: [u8; 32],
+    }
+
+    impl Hash for MockHash {
+        fn name() -> &'static str {
+            "mock"
+        }
+        type Block = [u8; 64];
+        type Output = [u8; 32];
+
+        fn input(&mut self, data: &[u8]) {
+            for (i, b) in data.iter().enumerate() {
+                self.state[i % 32] ^= b.wrapping_add(i as u8);
+            }
+        }
+
+        fn result(&mut self) -> Self::Output {
+            self.state
+        }
+    }
+
+    #[test]
+    fn verify_mac1_accepts_its_own_mac1() {
+        let keys = MacKeys::<MockHash>::new(b"responder-static-pubkey", &MacConfig::default());
+        let msg = b"handshake message body";
+        let mut out = vec![0u8; msg.len() + MAC_LEN];
+        keys.append_mac1(msg, &mut out);
+
+        assert!(keys.verify_mac1(&out, msg.len()));
+    }
+
+    #[test]
+    fn verify_mac1_still_matches_when_mac2_is_also_present() {
+        let keys = MacKeys::<MockHash>::new(b"responder-static-pubkey", &MacConfig::default());
+        let msg = b"handshake message body";
+        let cookie = keys.issue_cookie(&<MockHash as Hash>::Output::new_with(7), b"1.2.3.4:1234");
+        let mut out = vec![0u8; msg.len() + 2 * MAC_LEN];
+        keys.append_macs(msg, cookie.as_slice(), &mut out);
+
+        assert!(keys.verify_mac1(&out, msg.len()));
+        assert!(keys.verify_mac2(&out, cookie.as_slice()));
+    }
+
+    #[test]
+    fn verify_mac1_rejects_tampered_message() {
+        let keys = MacKeys::<MockHash>::new(b"responder-static-pubkey", &MacConfig::default());
+        let msg = b"handshake message body";
+        let mut out = vec![0u8; msg.len() + MAC_LEN];
+        keys.append_mac1(msg, &mut out);
+        out[0] ^= 1;
+
+        assert!(!keys.verify_mac1(&out, msg.len()));
+    }
+}
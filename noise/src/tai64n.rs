@@ -0,0 +1,63 @@
+//! TAI64N timestamps, used to defeat replay of identity-hiding initiation
+//! messages (`IK`/`K`-style patterns) without requiring any session state.
+
+/// A TAI64N timestamp: seconds since the TAI epoch, big-endian, followed by
+/// nanoseconds, big-endian.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Tai64N {
+    /// Seconds since the TAI epoch.
+    pub seconds: u64,
+    /// Nanoseconds within the second.
+    pub nanos: u32,
+}
+
+impl Tai64N {
+    /// Length in bytes of the encoded form.
+    pub const LEN: usize = 12;
+
+    /// Decode a big-endian 12-byte TAI64N timestamp.
+    pub fn from_bytes(b: &[u8]) -> Option<Self> {
+        if b.len() != Self::LEN {
+            return None;
+        }
+        let mut seconds = [0u8; 8];
+        seconds.copy_from_slice(&b[0..8]);
+        let mut nanos = [0u8; 4];
+        nanos.copy_from_slice(&b[8..12]);
+        Some(Tai64N {
+            seconds: u64::from_be_bytes(seconds),
+            nanos: u32::from_be_bytes(nanos),
+        })
+    }
+
+    /// Encode as a big-endian 12-byte TAI64N timestamp.
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut out = [0u8; Self::LEN];
+        out[0..8].copy_from_slice(&self.seconds.to_be_bytes());
+        out[8..12].copy_from_slice(&self.nanos.to_be_bytes());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let t = Tai64N { seconds: 0x0123_4567_89ab_cdef, nanos: 123_456_789 };
+        assert_eq!(Tai64N::from_bytes(&t.to_bytes()), Some(t));
+    }
+
+    #[test]
+    fn from_bytes_rejects_wrong_length() {
+        assert_eq!(Tai64N::from_bytes(&[0u8; Tai64N::LEN - 1]), None);
+    }
+
+    #[test]
+    fn orders_by_seconds_then_nanos() {
+        let earlier = Tai64N { seconds: 10, nanos: 999 };
+        let later = Tai64N { seconds: 11, nanos: 0 };
+        assert!(earlier < later);
+    }
+}
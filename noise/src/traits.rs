@@ -1,20 +1,20 @@
 /// A trait for fixed size u8 array.
-
-// Inspired by ArrayVec and SmallVec, but no unsafe.
-
-// Use this trait so that we don't have to use `Vec` for some semi-fixed length buffers and
-// input/output types.
+///
+/// Inspired by ArrayVec and SmallVec, but no unsafe.
+///
+/// Use this trait so that we don't have to use `Vec` for some semi-fixed length buffers and
+/// input/output types.
 pub trait U8Array {
     /// Create a new array filled with all zeros.
     fn new() -> Self;
     /// Create a new array filled with a same value.
-    fn new_with(u8) -> Self;
+    fn new_with(_: u8) -> Self;
     /// Create a new array from a slice.
     ///
     /// # Panics
     ///
     /// The slice must be of the same length.
-    fn from_slice(&[u8]) -> Self;
+    fn from_slice(_: &[u8]) -> Self;
     /// Length of the array.
     fn len() -> usize;
     /// As slice.
@@ -55,6 +55,7 @@ macro_rules! impl_array {
     }
 }
 
+impl_array!(16);
 impl_array!(32);
 impl_array!(64);
 impl_array!(128);
@@ -75,10 +76,10 @@ pub trait DH {
     fn genkey() -> Self::Key;
 
     /// Calculate public key from a private key.
-    fn pubkey(&Self::Key) -> Self::Pubkey;
+    fn pubkey(_: &Self::Key) -> Self::Pubkey;
 
     /// Perform DH key exchange.
-    fn dh(&Self::Key, &Self::Pubkey) -> Self::Output;
+    fn dh(_: &Self::Key, _: &Self::Pubkey) -> Self::Output;
 }
 
 /// An AEAD.
@@ -108,6 +109,7 @@ pub trait Cipher {
     /// AEAD decryption.
     ///
     /// out.len() == ciphertext.len() - Self::tag_len()
+    #[allow(clippy::result_unit_err)]
     fn decrypt(k: &Self::Key,
                nonce: u64,
                ad: &[u8],
@@ -161,9 +163,9 @@ pub trait Hash: Default {
         let mut ipad = Self::Block::new_with(0x36u8);
         let mut opad = Self::Block::new_with(0x5cu8);
 
-        for count in 0..key.len() {
-            ipad.as_mut()[count] ^= key[count];
-            opad.as_mut()[count] ^= key[count];
+        for (count, k) in key.iter().enumerate() {
+            ipad.as_mut()[count] ^= k;
+            opad.as_mut()[count] ^= k;
         }
 
         let mut hasher: Self = Default::default();
@@ -191,4 +193,17 @@ pub trait Hash: Default {
         let out2 = Self::hmac_many(temp_key.as_slice(), &[out1.as_slice(), &[2u8]]);
         (out1, out2)
     }
+
+    /// Calculate HKDF with 3 outputs, as specified in the noise spec for
+    /// `MixKeyAndHash` (used when processing a `PSK` token).
+    fn hkdf3(
+        chaining_key: &[u8],
+        input_key_material: &[u8],
+    ) -> (Self::Output, Self::Output, Self::Output) {
+        let temp_key = Self::hmac(chaining_key, input_key_material);
+        let out1 = Self::hmac(temp_key.as_slice(), &[1u8]);
+        let out2 = Self::hmac_many(temp_key.as_slice(), &[out1.as_slice(), &[2u8]]);
+        let out3 = Self::hmac_many(temp_key.as_slice(), &[out2.as_slice(), &[3u8]]);
+        (out1, out2, out3)
+    }
 }